@@ -1,10 +1,34 @@
-use std::{any::Any, collections::HashMap, hash::Hash};
+#![cfg_attr(not(feature = "std"), no_std)]
 
-pub struct AnyMap<K>
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use std::{
+    any::Any,
+    borrow::Borrow,
+    collections::{hash_map, hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+#[cfg(feature = "std")]
+mod multi;
+
+#[cfg(not(feature = "std"))]
+mod no_std;
+
+#[cfg(feature = "std")]
+pub use multi::MultiMap;
+
+#[cfg(not(feature = "std"))]
+pub use no_std::AnyMap;
+
+#[cfg(feature = "std")]
+pub struct AnyMap<K, S = RandomState>
 where
     K: Eq + Hash,
 {
-    h: HashMap<K, Box<dyn Any>>,
+    h: HashMap<K, Box<dyn Any>, S>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -13,6 +37,7 @@ pub enum Error {
     TypeMismatch,
 }
 
+#[cfg(feature = "std")]
 impl<K> AnyMap<K>
 where
     K: Eq + Hash,
@@ -20,13 +45,50 @@ where
     pub fn new() -> AnyMap<K> {
         AnyMap { h: HashMap::new() }
     }
+}
 
-    pub fn contains_key(&self, k: &K) -> bool {
+#[cfg(feature = "std")]
+impl<K, S> AnyMap<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hasher: S) -> AnyMap<K, S> {
+        AnyMap {
+            h: HashMap::with_hasher(hasher),
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> AnyMap<K, S> {
+        AnyMap {
+            h: HashMap::with_capacity_and_hasher(capacity, hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.h.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.h.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.h.clear()
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
         self.h.contains_key(k)
     }
 
-    pub fn contains_key_typed<V>(&self, k: &K) -> Result<(), Error>
+    pub fn contains_key_typed<V, Q>(&self, k: &Q) -> Result<(), Error>
     where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
         V: 'static,
     {
         match self.h.get(k) {
@@ -36,8 +98,10 @@ where
         }
     }
 
-    pub fn get<V>(&self, k: &K) -> Result<&V, Error>
+    pub fn get<V, Q>(&self, k: &Q) -> Result<&V, Error>
     where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
         V: 'static,
     {
         self.h
@@ -46,13 +110,43 @@ where
             .and_then(|b| b.downcast_ref::<V>().ok_or(Error::TypeMismatch))
     }
 
-    pub fn get_clone<V>(&mut self, k: &K) -> Result<V, Error>
+    pub fn get_clone<V, Q>(&mut self, k: &Q) -> Result<V, Error>
     where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
         V: Clone + 'static,
     {
         self.get(k).map(|b: &V| (*b).clone())
     }
 
+    pub fn get_mut<V, Q>(&mut self, k: &Q) -> Result<&mut V, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        self.h
+            .get_mut(k)
+            .ok_or(Error::KeyNotFound)
+            .and_then(|b| b.downcast_mut::<V>().ok_or(Error::TypeMismatch))
+    }
+
+    pub fn entry<V>(&mut self, k: K) -> Entry<'_, K, V>
+    where
+        V: 'static,
+    {
+        match self.h.entry(k) {
+            hash_map::Entry::Occupied(o) => Entry::Occupied(OccupiedEntry {
+                inner: o,
+                _marker: PhantomData,
+            }),
+            hash_map::Entry::Vacant(v) => Entry::Vacant(VacantEntry {
+                inner: v,
+                _marker: PhantomData,
+            }),
+        }
+    }
+
     pub fn insert<V>(&mut self, k: K, v: V) -> Result<Option<Box<V>>, (Error, V)>
     where
         V: 'static,
@@ -69,30 +163,126 @@ where
             .map(|b| b.downcast::<V>().unwrap()))
     }
 
-    pub fn remove<V>(&mut self, k: &K) -> Result<Box<V>, Error>
+    pub fn remove<V, Q>(&mut self, k: &Q) -> Result<Box<V>, Error>
     where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
         V: 'static,
     {
         let prev = self.h.get(k).ok_or(Error::KeyNotFound)?;
         if !prev.is::<V>() {
             return Err(Error::TypeMismatch);
         }
-        drop(prev);
 
         Ok(self.h.remove(k).unwrap().downcast::<V>().unwrap())
     }
+
+    pub fn iter_typed<V>(&self) -> impl Iterator<Item = (&K, &V)>
+    where
+        V: 'static,
+    {
+        self.h
+            .iter()
+            .filter_map(|(k, b)| b.downcast_ref::<V>().map(|v| (k, v)))
+    }
+
+    pub fn keys_of_type<V>(&self) -> impl Iterator<Item = &K>
+    where
+        V: 'static,
+    {
+        self.iter_typed::<V>().map(|(k, _)| k)
+    }
 }
 
-impl<K> Default for AnyMap<K>
+#[cfg(feature = "std")]
+impl<K, S> Default for AnyMap<K, S>
 where
     K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn default() -> AnyMap<K, S> {
+        AnyMap::with_hasher(S::default())
+    }
+}
+
+/// A view into a single entry of an `AnyMap`, tied to a concrete value type `V`,
+/// obtained via [`AnyMap::entry`].
+#[cfg(feature = "std")]
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+#[cfg(feature = "std")]
+pub struct OccupiedEntry<'a, K, V> {
+    inner: hash_map::OccupiedEntry<'a, K, Box<dyn Any>>,
+    _marker: PhantomData<V>,
+}
+
+#[cfg(feature = "std")]
+pub struct VacantEntry<'a, K, V> {
+    inner: hash_map::VacantEntry<'a, K, Box<dyn Any>>,
+    _marker: PhantomData<V>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V> OccupiedEntry<'a, K, V>
+where
+    V: 'static,
+{
+    pub fn into_mut_typed(self) -> Result<&'a mut V, Error> {
+        self.inner
+            .into_mut()
+            .downcast_mut::<V>()
+            .ok_or(Error::TypeMismatch)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V> VacantEntry<'a, K, V>
+where
+    V: 'static,
+{
+    pub fn insert(self, v: V) -> &'a mut V {
+        self.inner.insert(Box::new(v)).downcast_mut::<V>().unwrap()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V> Entry<'a, K, V>
+where
+    V: 'static,
 {
-    fn default() -> AnyMap<K> {
-        AnyMap::new()
+    /// Like [`Entry::or_insert`], but surfaces [`Error::TypeMismatch`] instead of
+    /// panicking when the entry is occupied by a value of a different type.
+    pub fn or_insert_typed(self, default: V) -> Result<&'a mut V, (Error, V)> {
+        match self {
+            Entry::Occupied(o) => o.into_mut_typed().map_err(|e| (e, default)),
+            Entry::Vacant(v) => Ok(v.insert(default)),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self.or_insert_typed(default) {
+            Ok(v) => v,
+            Err((_, _)) => panic!("AnyMap: entry is occupied by a value of a different type"),
+        }
+    }
+
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            Entry::Occupied(o) => o.into_mut_typed().unwrap_or_else(|_| {
+                panic!("AnyMap: entry is occupied by a value of a different type")
+            }),
+            Entry::Vacant(v) => v.insert(f()),
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::{AnyMap, Error};
 
@@ -101,9 +291,12 @@ mod tests {
         let mut m = AnyMap::<&'static str>::new();
 
         assert_eq!(false, m.contains_key(&"foo"));
-        assert_eq!(Err(Error::KeyNotFound), m.contains_key_typed::<u32>(&"foo"));
-        assert_eq!(Err(Error::KeyNotFound), m.get::<u32>(&"foo"));
-        assert_eq!(Err(Error::KeyNotFound), m.remove::<u32>(&"foo"));
+        assert_eq!(
+            Err(Error::KeyNotFound),
+            m.contains_key_typed::<u32, _>(&"foo")
+        );
+        assert_eq!(Err(Error::KeyNotFound), m.get::<u32, _>(&"foo"));
+        assert_eq!(Err(Error::KeyNotFound), m.remove::<u32, _>(&"foo"));
     }
 
     #[test]
@@ -116,11 +309,11 @@ mod tests {
         );
 
         assert_eq!(true, m.contains_key(&"foo"));
-        assert_eq!(Ok(()), m.contains_key_typed::<u32>(&"foo"));
-        assert_eq!(Ok(42), m.get::<u32>(&"foo").map(|r| *r));
-        assert_eq!(Ok(42), m.get_clone::<u32>(&"foo"));
-        assert_eq!(Ok(42), m.remove::<u32>(&"foo").map(|b| *b));
-        assert_eq!(Err(Error::KeyNotFound), m.remove::<u32>(&"foo"));
+        assert_eq!(Ok(()), m.contains_key_typed::<u32, _>(&"foo"));
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+        assert_eq!(Ok(42), m.get_clone::<u32, _>(&"foo"));
+        assert_eq!(Ok(42), m.remove::<u32, _>(&"foo").map(|b| *b));
+        assert_eq!(Err(Error::KeyNotFound), m.remove::<u32, _>(&"foo"));
     }
 
     #[test]
@@ -135,9 +328,111 @@ mod tests {
         assert_eq!(true, m.contains_key(&"foo"));
         assert_eq!(
             Err(Error::TypeMismatch),
-            m.contains_key_typed::<bool>(&"foo")
+            m.contains_key_typed::<bool, _>(&"foo")
         );
-        assert_eq!(Err(Error::TypeMismatch), m.get::<bool>(&"foo"));
-        assert_eq!(Err(Error::TypeMismatch), m.remove::<bool>(&"foo"));
+        assert_eq!(Err(Error::TypeMismatch), m.get::<bool, _>(&"foo"));
+        assert_eq!(Err(Error::TypeMismatch), m.remove::<bool, _>(&"foo"));
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut m = AnyMap::<&'static str>::new();
+        assert_eq!(Ok(None), m.insert::<u32>(&"foo", 1));
+
+        *m.get_mut::<u32, _>(&"foo").unwrap() += 41;
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+
+        assert_eq!(Err(Error::TypeMismatch), m.get_mut::<bool, _>(&"foo"));
+        assert_eq!(Err(Error::KeyNotFound), m.get_mut::<u32, _>(&"bar"));
+    }
+
+    #[test]
+    fn entry_vacant() {
+        let mut m = AnyMap::<&'static str>::new();
+
+        assert_eq!(42, *m.entry::<u32>(&"foo").or_insert(42));
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+
+        assert_eq!(
+            42,
+            m.entry::<u32>(&"bar").or_insert_with(|| 41).wrapping_add(1)
+        );
+    }
+
+    #[test]
+    fn entry_occupied() {
+        let mut m = AnyMap::<&'static str>::new();
+        assert_eq!(Ok(None), m.insert::<u32>(&"foo", 1));
+
+        assert_eq!(1, *m.entry::<u32>(&"foo").or_insert(42));
+        *m.entry::<u32>(&"foo").or_insert(0) += 1;
+        assert_eq!(Ok(2), m.get::<u32, _>(&"foo").map(|r| *r));
+
+        assert_eq!(
+            Err((Error::TypeMismatch, true)),
+            m.entry::<bool>(&"foo").or_insert_typed(true)
+        );
+    }
+
+    #[test]
+    fn custom_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+
+        let mut m: AnyMap<&'static str, BuildHasherDefault<DefaultHasher>> =
+            AnyMap::with_hasher(BuildHasherDefault::default());
+        assert_eq!(Ok(None), m.insert::<u32>(&"foo", 42));
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+
+        let m: AnyMap<&'static str, BuildHasherDefault<DefaultHasher>> =
+            AnyMap::with_capacity_and_hasher(8, BuildHasherDefault::default());
+        assert_eq!(false, m.contains_key(&"foo"));
+    }
+
+    #[test]
+    fn borrowed_lookup() {
+        let mut m = AnyMap::<String>::new();
+        assert_eq!(Ok(None), m.insert::<u32>("foo".to_string(), 42));
+
+        assert_eq!(true, m.contains_key("foo"));
+        assert_eq!(Ok(()), m.contains_key_typed::<u32, _>("foo"));
+        assert_eq!(Ok(42), m.get::<u32, _>("foo").map(|r| *r));
+        assert_eq!(Ok(42), m.get_clone::<u32, _>("foo"));
+        *m.get_mut::<u32, _>("foo").unwrap() += 1;
+        assert_eq!(Ok(43), m.remove::<u32, _>("foo").map(|b| *b));
+    }
+
+    #[test]
+    fn len_and_clear() {
+        let mut m = AnyMap::<&'static str>::new();
+        assert_eq!(0, m.len());
+        assert_eq!(true, m.is_empty());
+
+        assert_eq!(Ok(None), m.insert::<u32>(&"foo", 1));
+        assert_eq!(Ok(None), m.insert::<bool>(&"bar", true));
+        assert_eq!(2, m.len());
+        assert_eq!(false, m.is_empty());
+
+        m.clear();
+        assert_eq!(0, m.len());
+        assert_eq!(true, m.is_empty());
+    }
+
+    #[test]
+    fn iter_typed() {
+        let mut m = AnyMap::<&'static str>::new();
+        assert_eq!(Ok(None), m.insert::<u32>(&"foo", 1));
+        assert_eq!(Ok(None), m.insert::<u32>(&"bar", 2));
+        assert_eq!(Ok(None), m.insert::<bool>(&"baz", true));
+
+        let mut u32s: Vec<(&&str, &u32)> = m.iter_typed::<u32>().collect();
+        u32s.sort();
+        assert_eq!(vec![(&"bar", &2), (&"foo", &1)], u32s);
+
+        let mut keys: Vec<&&str> = m.keys_of_type::<u32>().collect();
+        keys.sort();
+        assert_eq!(vec![&"bar", &"foo"], keys);
+
+        assert_eq!(1, m.keys_of_type::<bool>().count());
     }
 }
@@ -0,0 +1,199 @@
+use std::{
+    any::{Any, TypeId},
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+use crate::Error;
+
+/// A type map that, unlike [`AnyMap`](crate::AnyMap), allows several
+/// differently-typed values to coexist under the same key.
+///
+/// Internally this is a two-level map, `K -> TypeId -> Box<dyn Any>`, so
+/// `insert::<u32>("foo", 1)` and `insert::<bool>("foo", true)` do not
+/// conflict. Keeping the levels separate (rather than keying on `(K,
+/// TypeId)` directly) means lookups only ever need to borrow `k`, the same
+/// as [`AnyMap`](crate::AnyMap)'s `Borrow`-based accessors.
+pub struct MultiMap<K, S = RandomState>
+where
+    K: Eq + Hash,
+{
+    h: HashMap<K, HashMap<TypeId, Box<dyn Any>>, S>,
+}
+
+impl<K> MultiMap<K>
+where
+    K: Eq + Hash,
+{
+    pub fn new() -> MultiMap<K> {
+        MultiMap { h: HashMap::new() }
+    }
+}
+
+impl<K, S> MultiMap<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hasher: S) -> MultiMap<K, S> {
+        MultiMap {
+            h: HashMap::with_hasher(hasher),
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> MultiMap<K, S> {
+        MultiMap {
+            h: HashMap::with_capacity_and_hasher(capacity, hasher),
+        }
+    }
+
+    pub fn contains_key<V, Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        self.h
+            .get(k)
+            .is_some_and(|types| types.contains_key(&TypeId::of::<V>()))
+    }
+
+    pub fn get<V, Q>(&self, k: &Q) -> Result<&V, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        self.h
+            .get(k)
+            .and_then(|types| types.get(&TypeId::of::<V>()))
+            .ok_or(Error::KeyNotFound)
+            .map(|b| b.downcast_ref::<V>().unwrap())
+    }
+
+    pub fn insert<V>(&mut self, k: K, v: V) -> Option<Box<V>>
+    where
+        V: 'static,
+    {
+        self.h
+            .entry(k)
+            .or_default()
+            .insert(TypeId::of::<V>(), Box::new(v))
+            .map(|b| b.downcast::<V>().unwrap())
+    }
+
+    pub fn remove<V, Q>(&mut self, k: &Q) -> Result<Box<V>, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        let types = self.h.get_mut(k).ok_or(Error::KeyNotFound)?;
+        let removed = types.remove(&TypeId::of::<V>()).ok_or(Error::KeyNotFound)?;
+
+        if types.is_empty() {
+            self.h.remove(k);
+        }
+
+        Ok(removed.downcast::<V>().unwrap())
+    }
+
+    /// Removes every value stored under `k`, regardless of type.
+    pub fn remove_all<Q>(&mut self, k: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.h.remove(k);
+    }
+
+    /// Iterates over the types currently stored under `k`.
+    pub fn types_at<'a, Q>(&'a self, k: &'a Q) -> impl Iterator<Item = TypeId> + 'a
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.h
+            .get(k)
+            .into_iter()
+            .flat_map(|types| types.keys().copied())
+    }
+}
+
+impl<K, S> Default for MultiMap<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn default() -> MultiMap<K, S> {
+        MultiMap::with_hasher(S::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiMap;
+    use crate::Error;
+
+    #[test]
+    fn coexisting_types() {
+        let mut m = MultiMap::<&'static str>::new();
+
+        assert_eq!(None, m.insert::<u32>("foo", 1));
+        assert_eq!(None, m.insert::<bool>("foo", true));
+
+        assert_eq!(true, m.contains_key::<u32, _>(&"foo"));
+        assert_eq!(true, m.contains_key::<bool, _>(&"foo"));
+        assert_eq!(Ok(1), m.get::<u32, _>(&"foo").map(|r| *r));
+        assert_eq!(Ok(true), m.get::<bool, _>(&"foo").map(|r| *r));
+
+        assert_eq!(2, m.types_at(&"foo").count());
+
+        assert_eq!(Ok(true), m.remove::<bool, _>(&"foo").map(|b| *b));
+        assert_eq!(false, m.contains_key::<bool, _>(&"foo"));
+        assert_eq!(true, m.contains_key::<u32, _>(&"foo"));
+
+        m.remove_all(&"foo");
+        assert_eq!(false, m.contains_key::<u32, _>(&"foo"));
+    }
+
+    #[test]
+    fn missing_key() {
+        let m = MultiMap::<&'static str>::new();
+
+        assert_eq!(false, m.contains_key::<u32, _>(&"foo"));
+        assert_eq!(Err(Error::KeyNotFound), m.get::<u32, _>(&"foo"));
+    }
+
+    #[test]
+    fn borrowed_lookup() {
+        let mut m = MultiMap::<String>::new();
+
+        assert_eq!(None, m.insert::<u32>("foo".to_string(), 1));
+        assert_eq!(None, m.insert::<bool>("foo".to_string(), true));
+
+        assert_eq!(true, m.contains_key::<u32, _>("foo"));
+        assert_eq!(Ok(1), m.get::<u32, _>("foo").map(|r| *r));
+        assert_eq!(Ok(true), m.remove::<bool, _>("foo").map(|b| *b));
+        assert_eq!(true, m.contains_key::<u32, _>("foo"));
+
+        m.remove_all("foo");
+        assert_eq!(false, m.contains_key::<u32, _>("foo"));
+    }
+
+    #[test]
+    fn custom_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+
+        let mut m: MultiMap<&'static str, BuildHasherDefault<DefaultHasher>> =
+            MultiMap::with_hasher(BuildHasherDefault::default());
+        assert_eq!(None, m.insert::<u32>("foo", 42));
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+
+        let m: MultiMap<&'static str, BuildHasherDefault<DefaultHasher>> =
+            MultiMap::with_capacity_and_hasher(8, BuildHasherDefault::default());
+        assert_eq!(false, m.contains_key::<u32, _>(&"foo"));
+    }
+}
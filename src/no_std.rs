@@ -0,0 +1,271 @@
+//! The `no_std` counterpart of the top-level `AnyMap`, built on `alloc` and
+//! backed by `hashbrown` instead of `std::collections::HashMap` so it has no
+//! dependency on the standard library.
+//!
+//! Unlike the `std` flavor, this one has no default hasher of its own
+//! (`hashbrown`'s default hasher sits behind a cargo feature this crate
+//! doesn't enable), so `S` must always be supplied explicitly via
+//! [`AnyMap::new`] or [`AnyMap::with_hasher`]. It also lacks these parts of
+//! the `std` build: `contains_key_typed`, `get_clone`,
+//! `with_capacity_and_hasher`, `entry`/`Entry`, and `MultiMap`.
+
+use alloc::boxed::Box;
+use core::any::Any;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+
+use hashbrown::HashMap;
+
+use crate::Error;
+
+pub struct AnyMap<K, S>
+where
+    K: Eq + Hash,
+{
+    h: HashMap<K, Box<dyn Any>, S>,
+}
+
+impl<K, S> AnyMap<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    pub fn new() -> AnyMap<K, S> {
+        AnyMap::with_hasher(S::default())
+    }
+}
+
+impl<K, S> AnyMap<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hasher: S) -> AnyMap<K, S> {
+        AnyMap {
+            h: HashMap::with_hasher(hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.h.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.h.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.h.clear()
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.h.contains_key(k)
+    }
+
+    pub fn get<V, Q>(&self, k: &Q) -> Result<&V, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        self.h
+            .get(k)
+            .ok_or(Error::KeyNotFound)
+            .and_then(|b| b.downcast_ref::<V>().ok_or(Error::TypeMismatch))
+    }
+
+    pub fn get_mut<V, Q>(&mut self, k: &Q) -> Result<&mut V, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        self.h
+            .get_mut(k)
+            .ok_or(Error::KeyNotFound)
+            .and_then(|b| b.downcast_mut::<V>().ok_or(Error::TypeMismatch))
+    }
+
+    pub fn insert<V>(&mut self, k: K, v: V) -> Result<Option<Box<V>>, (Error, V)>
+    where
+        V: 'static,
+    {
+        if let Some(prev) = self.h.get(&k) {
+            if !prev.is::<V>() {
+                return Err((Error::TypeMismatch, v));
+            }
+        }
+
+        Ok(self
+            .h
+            .insert(k, Box::new(v))
+            .map(|b| b.downcast::<V>().unwrap()))
+    }
+
+    pub fn remove<V, Q>(&mut self, k: &Q) -> Result<Box<V>, Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: 'static,
+    {
+        let prev = self.h.get(k).ok_or(Error::KeyNotFound)?;
+        if !prev.is::<V>() {
+            return Err(Error::TypeMismatch);
+        }
+
+        Ok(self.h.remove(k).unwrap().downcast::<V>().unwrap())
+    }
+
+    pub fn iter_typed<V>(&self) -> impl Iterator<Item = (&K, &V)>
+    where
+        V: 'static,
+    {
+        self.h
+            .iter()
+            .filter_map(|(k, b)| b.downcast_ref::<V>().map(|v| (k, v)))
+    }
+
+    pub fn keys_of_type<V>(&self) -> impl Iterator<Item = &K>
+    where
+        V: 'static,
+    {
+        self.iter_typed::<V>().map(|(k, _)| k)
+    }
+}
+
+impl<K, S> Default for AnyMap<K, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn default() -> AnyMap<K, S> {
+        AnyMap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnyMap, Error};
+    use core::hash::{BuildHasherDefault, Hasher};
+
+    /// A tiny FNV-1a `Hasher` so these tests don't need an extra dependency
+    /// just to pick a `BuildHasher` (`hashbrown`'s own default hasher sits
+    /// behind a cargo feature this crate doesn't enable).
+    #[derive(Default)]
+    struct FnvHasher(u64);
+
+    impl Hasher for FnvHasher {
+        fn write(&mut self, bytes: &[u8]) {
+            const PRIME: u64 = 0x100000001b3;
+            let mut hash = if self.0 == 0 {
+                0xcbf29ce484222325
+            } else {
+                self.0
+            };
+            for byte in bytes {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(PRIME);
+            }
+            self.0 = hash;
+        }
+
+        fn finish(&self) -> u64 {
+            self.0
+        }
+    }
+
+    type Fnv = BuildHasherDefault<FnvHasher>;
+
+    #[test]
+    fn missing_key() {
+        let mut m = AnyMap::<&'static str, Fnv>::new();
+
+        assert_eq!(false, m.contains_key(&"foo"));
+        assert_eq!(Err(Error::KeyNotFound), m.get::<u32, _>(&"foo"));
+        assert_eq!(Err(Error::KeyNotFound), m.remove::<u32, _>(&"foo"));
+    }
+
+    #[test]
+    fn normal() {
+        let mut m = AnyMap::<&'static str, Fnv>::new();
+        assert_eq!(Ok(None), m.insert::<u32>("foo", 1).map(|r| r.map(|b| *b)));
+        assert_eq!(
+            Ok(Some(1)),
+            m.insert::<u32>("foo", 42).map(|r| r.map(|b| *b))
+        );
+
+        assert_eq!(true, m.contains_key(&"foo"));
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+        assert_eq!(Ok(42), m.remove::<u32, _>(&"foo").map(|b| *b));
+        assert_eq!(Err(Error::KeyNotFound), m.remove::<u32, _>(&"foo"));
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let mut m = AnyMap::<&'static str, Fnv>::new();
+        assert_eq!(Ok(None), m.insert::<u32>("foo", 42).map(|r| r.map(|b| *b)));
+        assert_eq!(
+            Err(Error::TypeMismatch),
+            m.insert::<bool>("foo", true).map_err(|(err, _)| err)
+        );
+
+        assert_eq!(Err(Error::TypeMismatch), m.get::<bool, _>(&"foo"));
+        assert_eq!(Err(Error::TypeMismatch), m.remove::<bool, _>(&"foo"));
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut m = AnyMap::<&'static str, Fnv>::new();
+        assert_eq!(Ok(None), m.insert::<u32>("foo", 1).map(|r| r.map(|b| *b)));
+
+        *m.get_mut::<u32, _>(&"foo").unwrap() += 41;
+        assert_eq!(Ok(42), m.get::<u32, _>(&"foo").map(|r| *r));
+
+        assert_eq!(Err(Error::TypeMismatch), m.get_mut::<bool, _>(&"foo"));
+        assert_eq!(Err(Error::KeyNotFound), m.get_mut::<u32, _>(&"bar"));
+    }
+
+    #[test]
+    fn len_and_clear() {
+        let mut m = AnyMap::<&'static str, Fnv>::new();
+        assert_eq!(0, m.len());
+        assert_eq!(true, m.is_empty());
+
+        assert_eq!(Ok(None), m.insert::<u32>("foo", 1).map(|r| r.map(|b| *b)));
+        assert_eq!(
+            Ok(None),
+            m.insert::<bool>("bar", true).map(|r| r.map(|b| *b))
+        );
+        assert_eq!(2, m.len());
+        assert_eq!(false, m.is_empty());
+
+        m.clear();
+        assert_eq!(0, m.len());
+        assert_eq!(true, m.is_empty());
+    }
+
+    #[test]
+    fn iter_typed() {
+        let mut m = AnyMap::<&'static str, Fnv>::new();
+        assert_eq!(Ok(None), m.insert::<u32>("foo", 1).map(|r| r.map(|b| *b)));
+        assert_eq!(Ok(None), m.insert::<u32>("bar", 2).map(|r| r.map(|b| *b)));
+        assert_eq!(
+            Ok(None),
+            m.insert::<bool>("baz", true).map(|r| r.map(|b| *b))
+        );
+
+        let mut u32s: alloc::vec::Vec<(&&str, &u32)> = m.iter_typed::<u32>().collect();
+        u32s.sort();
+        assert_eq!(alloc::vec![(&"bar", &2), (&"foo", &1)], u32s);
+
+        let mut keys: alloc::vec::Vec<&&str> = m.keys_of_type::<u32>().collect();
+        keys.sort();
+        assert_eq!(alloc::vec![&"bar", &"foo"], keys);
+
+        assert_eq!(1, m.keys_of_type::<bool>().count());
+    }
+}